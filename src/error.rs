@@ -1,21 +1,70 @@
 use thiserror::Error;
 
-use solana_program::program_error::ProgramError;
+use solana_program::{msg, program_error::ProgramError, pubkey::Pubkey};
+
+static LOG_TAG_NAME: &str = "[rental_agreement]";
 
 #[derive(Error, Debug, Copy, Clone)]
 pub enum RentalAgreementError {
     #[error("Rent Already Paid In Full")]
-    AlreadyPaidInFull = 100,
+    AlreadyPaidInFull,
 
     #[error("Rent Payment Amount Mistmatch")]
     PaymentAmountMismatch,
 
     #[error("Rent Agreement Terminated")]
     AgreementTerminated,
+
+    #[error("Deposit Escrow Account Invalid")]
+    InvalidEscrowAccount,
+
+    #[error("Deposit Refund Only Allowed Once Agreement Is Completed")]
+    AgreementNotCompleted,
+
+    #[error("Deposit Forfeiture Only Allowed Once Agreement Is Terminated")]
+    AgreementNotTerminated,
+
+    #[error("Account Left In An Invalid Rent Paying State")]
+    InvalidRentPayingAccount,
+
+    #[error("Account Is Not Writable: {0}")]
+    NonWriteableAccount(Pubkey),
+
+    #[error("Missing Required Signature")]
+    MissingSignature,
+
+    #[error("Account Has Unexpected Owner: {0}")]
+    InvalidOwner(Pubkey),
+
+    #[error("Rent Payment Not Yet Due")]
+    PaymentNotYetDue,
+
+    #[error("Lease Has Not Yet Expired")]
+    LeaseNotYetExpired,
 }
 
 impl From<RentalAgreementError> for ProgramError {
     fn from(e: RentalAgreementError) -> Self {
-        ProgramError::Custom(e as u32)
+        let code = match e {
+            RentalAgreementError::AlreadyPaidInFull => 100,
+            RentalAgreementError::PaymentAmountMismatch => 101,
+            RentalAgreementError::AgreementTerminated => 102,
+            RentalAgreementError::InvalidEscrowAccount => 103,
+            RentalAgreementError::AgreementNotCompleted => 104,
+            RentalAgreementError::AgreementNotTerminated => 105,
+            RentalAgreementError::InvalidRentPayingAccount => 106,
+            RentalAgreementError::NonWriteableAccount(pubkey) => {
+                msg!("{} Account {} is not writable", LOG_TAG_NAME, pubkey);
+                107
+            }
+            RentalAgreementError::MissingSignature => 108,
+            RentalAgreementError::InvalidOwner(pubkey) => {
+                msg!("{} Account {} has an unexpected owner", LOG_TAG_NAME, pubkey);
+                109
+            }
+            RentalAgreementError::PaymentNotYetDue => 110,
+            RentalAgreementError::LeaseNotYetExpired => 111,
+        };
+        ProgramError::Custom(code)
     }
 }
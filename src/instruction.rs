@@ -8,6 +8,9 @@ pub enum RentalInstruction {
     /// Accounts expected:
     /// 0. `[writable]` The Rent Agreement account created to manage state across 2 parties; owned by program id.
     /// 1. `[]` Sysvar Rent Account to validate rent exemption (SYSVAR_RENT_PUBKEY)
+    /// 2. `[writable]` The deposit escrow PDA account, derived from the agreement account key; created here.
+    /// 3. `[signer, writable]` Tenant account (keypair), funds the escrow account and the deposit.
+    /// 4. `[]` System program account
     Initialization {
         flat_owner_pubkey: Pubkey,
         tenant_pubkey: Pubkey,
@@ -30,7 +33,35 @@ pub enum RentalInstruction {
     ///
     /// Accounts expected:
     /// 0. `[writable]` The Rent Agreement account created to manage state across 2 parties; owned by program id.
+    /// 1. `[signer]` Authority account; must be either the flat owner or the tenant on the agreement.
     TerminationBeforeInitialDate {},
+
+    /// Refund the escrowed security deposit to the tenant once the agreement has
+    /// run its full course.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The Rent Agreement account created to manage state across 2 parties; owned by program id.
+    /// 1. `[writable]` The deposit escrow PDA account, derived from the agreement account key.
+    /// 2. `[writable]` Tenant account (public key), receives the refunded deposit.
+    RefundDeposit {},
+
+    /// Forfeit the escrowed security deposit to the flat owner after an early termination.
+    /// The create-time rent reserve funded by the tenant is returned to the tenant;
+    /// only the `deposit` amount itself is forfeited to the flat owner.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The Rent Agreement account created to manage state across 2 parties; owned by program id.
+    /// 1. `[writable]` The deposit escrow PDA account, derived from the agreement account key.
+    /// 2. `[writable]` Flat owner account (public key), receives the forfeited deposit.
+    /// 3. `[writable]` Tenant account (public key), receives back the escrow account's rent reserve.
+    ForfeitDeposit {},
+
+    /// Mark the agreement `Completed` once the full lease term has elapsed, even if
+    /// rent installments remain outstanding.
+    ///
+    /// Accounts expected:
+    /// 0. `[writable]` The Rent Agreement account created to manage state across 2 parties; owned by program id.
+    TerminationAfterExpiry {},
 }
 
 impl RentalInstruction {
@@ -41,11 +72,13 @@ impl RentalInstruction {
 
         Ok(match tag {
             0 => {
-                let flat_owner_pubkey: Pubkey = Pubkey::new(&rest[..32]);
-                let tenant_pubkey: Pubkey = Pubkey::new(&rest[32..64]);
-                let deposit: u64 = Self::unpack_u64(&rest, 64)?;
-                let rent_amount: u64 = Self::unpack_u64(&rest, 72)?;
-                let duration: u64 = Self::unpack_u64(&rest, 80)?;
+                let flat_owner_pubkey: Pubkey = Pubkey::try_from(&rest[..32])
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+                let tenant_pubkey: Pubkey = Pubkey::try_from(&rest[32..64])
+                    .map_err(|_| ProgramError::InvalidInstructionData)?;
+                let deposit: u64 = Self::unpack_u64(rest, 64)?;
+                let rent_amount: u64 = Self::unpack_u64(rest, 72)?;
+                let duration: u64 = Self::unpack_u64(rest, 80)?;
                 let duration_unit: u8 = rest[88];
 
                 Self::Initialization {
@@ -58,10 +91,13 @@ impl RentalInstruction {
                 }
             }
             1 => {
-                let rent_amount: u64 = Self::unpack_u64(&rest, 0)?;
+                let rent_amount: u64 = Self::unpack_u64(rest, 0)?;
                 Self::Payment { rent_amount }
             }
             2 => Self::TerminationBeforeInitialDate {},
+            3 => Self::RefundDeposit {},
+            4 => Self::ForfeitDeposit {},
+            5 => Self::TerminationAfterExpiry {},
             _ => return Err(ProgramError::InvalidInstructionData),
         })
     }
@@ -1,20 +1,21 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
+    clock::Clock,
     entrypoint::ProgramResult,
     msg,
-    program::invoke,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
     program_pack::IsInitialized,
     pubkey::Pubkey,
-    system_instruction,
+    system_instruction, system_program,
     sysvar::{rent::Rent, Sysvar},
 };
 
 use crate::{
     error::RentalAgreementError,
     instruction::RentalInstruction,
-    state::{AgreementStatus, RentalAgreementAccount},
+    state::{AgreementStatus, Duration, RentalAgreementAccount, RentState, ESCROW_SEED_PREFIX},
 };
 
 static LOG_TAG_NAME: &str = "[rental_agreement]";
@@ -54,9 +55,18 @@ impl Processor {
             RentalInstruction::TerminationBeforeInitialDate {} => {
                 Self::terminate_before_initial_date(accounts, program_id)
             }
+
+            RentalInstruction::RefundDeposit {} => Self::refund_deposit(accounts, program_id),
+
+            RentalInstruction::ForfeitDeposit {} => Self::forfeit_deposit(accounts, program_id),
+
+            RentalInstruction::TerminationAfterExpiry {} => {
+                Self::terminate_after_expiry(accounts, program_id)
+            }
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn initialize_rent_contract(
         accounts: &[AccountInfo],
         program_id: &Pubkey,
@@ -70,27 +80,102 @@ impl Processor {
         let accounts_iter = &mut accounts.iter();
 
         let rent_agreement_account = next_account_info(accounts_iter)?;
-        if rent_agreement_account.owner != program_id {
+        Self::validate_account(rent_agreement_account, Some(program_id), true, false)?;
+
+        let solana_rent = &Rent::from_account_info(next_account_info(accounts_iter)?)?;
+        if rent_agreement_account.data_len() != RentalAgreementAccount::LEN {
             msg!(
-                "{} Rent agreement account not owned by this program",
-                LOG_TAG_NAME
+                "{} Rent agreement account size does not match expected layout: {} vs {}",
+                LOG_TAG_NAME,
+                rent_agreement_account.data_len(),
+                RentalAgreementAccount::LEN
             );
-            return Err(ProgramError::IncorrectProgramId);
+            return Err(ProgramError::InvalidAccountData);
         }
+        Self::verify_rent_exemption(rent_agreement_account, solana_rent)?;
+
+        let escrow_account = next_account_info(accounts_iter)?;
+        let tenant_account = next_account_info(accounts_iter)?;
+        let system_program_account = next_account_info(accounts_iter)?;
+
+        Self::validate_account(tenant_account, None, true, true)?;
+
+        let (expected_escrow_key, escrow_bump) =
+            RentalAgreementAccount::escrow_pda(rent_agreement_account.key, program_id);
+        if escrow_account.key != &expected_escrow_key {
+            msg!("{} Deposit escrow account does not match the PDA derived from the agreement account", LOG_TAG_NAME);
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let rent_state_snapshot = [
+            (tenant_account, RentState::from_account(tenant_account, solana_rent)),
+            (escrow_account, RentState::from_account(escrow_account, solana_rent)),
+        ];
+
+        if escrow_account.owner != program_id {
+            // The escrow PDA's address is deterministic from the agreement account key,
+            // so an attacker could pre-fund it with lamports to try to dodge creation
+            // below. Refuse unless it is still the untouched, System-owned account the
+            // PDA derivation implies.
+            if !system_program::check_id(escrow_account.owner) || escrow_account.lamports() != 0 {
+                msg!(
+                    "{} Deposit escrow account must be untouched before creation",
+                    LOG_TAG_NAME
+                );
+                return Err(ProgramError::InvalidAccountData);
+            }
 
-        let solana_rent = &Rent::from_account_info(next_account_info(accounts_iter)?)?;
-        if !solana_rent.is_exempt(
-            rent_agreement_account.lamports(),
-            rent_agreement_account.data_len(),
-        ) {
             msg!(
-                "{} Rental agreement account not rent exempt on Solana; balance: {}",
+                "{} Creating deposit escrow account: {}",
                 LOG_TAG_NAME,
-                rent_agreement_account.lamports()
+                escrow_account.key
             );
-            return Err(ProgramError::AccountNotRentExempt);
+            invoke_signed(
+                &system_instruction::create_account(
+                    tenant_account.key,
+                    escrow_account.key,
+                    solana_rent.minimum_balance(0),
+                    0,
+                    program_id,
+                ),
+                &[
+                    tenant_account.clone(),
+                    escrow_account.clone(),
+                    system_program_account.clone(),
+                ],
+                &[&[
+                    ESCROW_SEED_PREFIX,
+                    rent_agreement_account.key.as_ref(),
+                    &[escrow_bump],
+                ]],
+            )?;
+
+            if escrow_account.owner != program_id {
+                msg!(
+                    "{} Deposit escrow account creation did not transfer ownership to this program",
+                    LOG_TAG_NAME
+                );
+                return Err(ProgramError::IncorrectProgramId);
+            }
         }
 
+        msg!(
+            "{} Escrowing deposit of {} lamports from tenant",
+            LOG_TAG_NAME,
+            deposit
+        );
+        invoke(
+            &system_instruction::transfer(tenant_account.key, escrow_account.key, deposit),
+            &[
+                tenant_account.clone(),
+                escrow_account.clone(),
+                system_program_account.clone(),
+            ],
+        )?;
+
+        Self::assert_valid_rent_transitions(solana_rent, &rent_state_snapshot)?;
+        Self::verify_rent_exemption(escrow_account, solana_rent)?;
+
         // Initialize the Rent Agreement Account with the initial data
         // Note: the structure of the data state must match the `space` reserved when account created
         let rent_agreement_data =
@@ -118,6 +203,14 @@ impl Processor {
         rent_data.duration = duration;
         rent_data.duration_unit = duration_unit;
         rent_data.remaining_payments = duration;
+        rent_data.escrow_bump = escrow_bump;
+        let interval_seconds = Duration::from_u8(duration_unit)
+            .ok_or(ProgramError::InvalidAccountData)?
+            .seconds();
+        rent_data.start_timestamp = Clock::get()?.unix_timestamp;
+        // Seed one interval before `start_timestamp` so the first rent installment is
+        // due at signing rather than a full interval into the lease.
+        rent_data.last_payment_timestamp = rent_data.start_timestamp - interval_seconds;
         rent_data.serialize(&mut &mut rent_agreement_account.data.borrow_mut()[..])?;
 
         msg!(
@@ -133,21 +226,13 @@ impl Processor {
         let accounts_iter = &mut accounts.iter();
 
         let rent_agreement_account = next_account_info(accounts_iter)?;
-        if rent_agreement_account.owner != program_id {
-            msg!(
-                "{}, Rent agreement account is not owned by this program",
-                LOG_TAG_NAME
-            );
-            return Err(ProgramError::IncorrectProgramId);
-        }
+        Self::validate_account(rent_agreement_account, Some(program_id), true, false)?;
 
         let flat_owner_account: &AccountInfo = next_account_info(accounts_iter)?;
         let tenant_account = next_account_info(accounts_iter)?;
         let system_program_account = next_account_info(accounts_iter)?;
 
-        if !tenant_account.is_signer {
-            return Err(ProgramError::MissingRequiredSignature);
-        }
+        Self::validate_account(tenant_account, None, true, true)?;
 
         if tenant_account.lamports() < rent_amount {
             return Err(ProgramError::InsufficientFunds);
@@ -214,8 +299,27 @@ impl Processor {
             return Err(RentalAgreementError::PaymentAmountMismatch.into());
         }
 
+        let now = Clock::get()?.unix_timestamp;
+        let interval_seconds = Duration::from_u8(rent_data.duration_unit)
+            .ok_or(ProgramError::InvalidAccountData)?
+            .seconds();
+        if now - rent_data.last_payment_timestamp < interval_seconds {
+            msg!(
+                "{} Rent payment not yet due; {} seconds remain",
+                LOG_TAG_NAME,
+                interval_seconds - (now - rent_data.last_payment_timestamp)
+            );
+            return Err(RentalAgreementError::PaymentNotYetDue.into());
+        }
+
+        let rent = Rent::get()?;
+        let rent_state_snapshot = [
+            (tenant_account, RentState::from_account(tenant_account, &rent)),
+            (flat_owner_account, RentState::from_account(flat_owner_account, &rent)),
+        ];
+
         let instruction =
-            system_instruction::transfer(&tenant_account.key, &flat_owner_account.key, rent_amount);
+            system_instruction::transfer(tenant_account.key, flat_owner_account.key, rent_amount);
 
         // Invoke the system program to transfer funds
         invoke(
@@ -227,6 +331,8 @@ impl Processor {
             ],
         )?;
 
+        Self::assert_valid_rent_transitions(&rent, &rent_state_snapshot)?;
+
         msg!(
             "{} Transfer completed. New payer balance: {}",
             LOG_TAG_NAME,
@@ -235,6 +341,7 @@ impl Processor {
 
         // Decrement the number of payment
         rent_data.remaining_payments -= 1;
+        rent_data.last_payment_timestamp = now;
         if rent_data.remaining_payments == 0 {
             rent_data.status = AgreementStatus::Completed as u8;
         }
@@ -250,14 +357,62 @@ impl Processor {
         let accounts_iter = &mut accounts.iter();
 
         let rent_agreement_account = next_account_info(accounts_iter)?;
-        if rent_agreement_account.owner != program_id {
+        Self::validate_account(rent_agreement_account, Some(program_id), true, false)?;
+
+        let authority_account = next_account_info(accounts_iter)?;
+        Self::validate_account(authority_account, None, false, true)?;
+
+        let rent_agreement_data =
+            RentalAgreementAccount::try_from_slice(&rent_agreement_account.data.borrow());
+
+        if rent_agreement_data.is_err() {
             msg!(
-                "{} Rent agreement account is not owned by this program",
+                "{} Rent agreement account data size incorrect: {}",
+                LOG_TAG_NAME,
+                rent_agreement_account.try_data_len()?
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut rent_data = rent_agreement_data.unwrap();
+        if !rent_data.is_initialized() {
+            msg!("{} Rent agreement account not initialized", LOG_TAG_NAME);
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        if authority_account.key != &rent_data.flat_owner_pubkey
+            && authority_account.key != &rent_data.tenant_pubkey
+        {
+            msg!(
+                "{} Termination authority must be the flat owner or the tenant",
                 LOG_TAG_NAME
             );
-            return Err(ProgramError::IncorrectProgramId);
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if rent_data.is_complete() {
+            msg!("{} Rent already paid in full", LOG_TAG_NAME);
+            return Err(RentalAgreementError::AlreadyPaidInFull.into());
         }
 
+        if rent_data.is_terminated() {
+            msg!("{} Rent agreement already terminated", LOG_TAG_NAME);
+            return Err(RentalAgreementError::AgreementTerminated.into());
+        }
+
+        rent_data.remaining_payments = 0;
+        rent_data.status = AgreementStatus::Terminated as u8;
+        rent_data.serialize(&mut &mut rent_agreement_account.data.borrow_mut()[..])?;
+
+        Ok(())
+    }
+
+    fn terminate_after_expiry(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let rent_agreement_account = next_account_info(accounts_iter)?;
+        Self::validate_account(rent_agreement_account, Some(program_id), true, false)?;
+
         let rent_agreement_data =
             RentalAgreementAccount::try_from_slice(&rent_agreement_account.data.borrow());
 
@@ -286,10 +441,319 @@ impl Processor {
             return Err(RentalAgreementError::AgreementTerminated.into());
         }
 
-        rent_data.remaining_payments = 0;
-        rent_data.status = AgreementStatus::Terminated as u8;
+        let expiry_timestamp = rent_data
+            .expiry_timestamp()
+            .ok_or(ProgramError::InvalidAccountData)?;
+        if Clock::get()?.unix_timestamp < expiry_timestamp {
+            msg!(
+                "{} Lease has not yet reached its expiry timestamp: {}",
+                LOG_TAG_NAME,
+                expiry_timestamp
+            );
+            return Err(RentalAgreementError::LeaseNotYetExpired.into());
+        }
+
+        rent_data.status = AgreementStatus::Completed as u8;
         rent_data.serialize(&mut &mut rent_agreement_account.data.borrow_mut()[..])?;
 
+        msg!("{} Lease expired; agreement marked completed", LOG_TAG_NAME);
+
+        Ok(())
+    }
+
+    fn refund_deposit(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let rent_agreement_account = next_account_info(accounts_iter)?;
+        Self::validate_account(rent_agreement_account, Some(program_id), true, false)?;
+
+        let escrow_account = next_account_info(accounts_iter)?;
+        let tenant_account = next_account_info(accounts_iter)?;
+        Self::validate_account(tenant_account, None, true, false)?;
+
+        let rent_data = RentalAgreementAccount::try_from_slice(&rent_agreement_account.data.borrow())
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+
+        if !rent_data.is_initialized() {
+            msg!("{} Rent agreement account not initialized", LOG_TAG_NAME);
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        if !rent_data.is_complete() {
+            msg!(
+                "{} Deposit can only be refunded once the agreement is completed",
+                LOG_TAG_NAME
+            );
+            return Err(RentalAgreementError::AgreementNotCompleted.into());
+        }
+
+        Self::verify_escrow_account(
+            escrow_account,
+            rent_agreement_account.key,
+            program_id,
+            rent_data.escrow_bump,
+        )?;
+
+        if tenant_account.key != &rent_data.tenant_pubkey {
+            msg!(
+                "{} Refund recipient must match tenant key used during agreement initialization",
+                LOG_TAG_NAME
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Self::settle_escrow(escrow_account, rent_data.deposit, tenant_account, tenant_account)?;
+
+        msg!("{} Deposit refunded to tenant", LOG_TAG_NAME);
+
         Ok(())
     }
+
+    fn forfeit_deposit(accounts: &[AccountInfo], program_id: &Pubkey) -> ProgramResult {
+        let accounts_iter = &mut accounts.iter();
+
+        let rent_agreement_account = next_account_info(accounts_iter)?;
+        Self::validate_account(rent_agreement_account, Some(program_id), true, false)?;
+
+        let escrow_account = next_account_info(accounts_iter)?;
+        let flat_owner_account = next_account_info(accounts_iter)?;
+        Self::validate_account(flat_owner_account, None, true, false)?;
+
+        let tenant_account = next_account_info(accounts_iter)?;
+        Self::validate_account(tenant_account, None, true, false)?;
+
+        let rent_data = RentalAgreementAccount::try_from_slice(&rent_agreement_account.data.borrow())
+            .map_err(|_| ProgramError::InvalidAccountData)?;
+
+        if !rent_data.is_initialized() {
+            msg!("{} Rent agreement account not initialized", LOG_TAG_NAME);
+            return Err(ProgramError::UninitializedAccount);
+        }
+
+        if !rent_data.is_terminated() {
+            msg!(
+                "{} Deposit can only be forfeited once the agreement is terminated",
+                LOG_TAG_NAME
+            );
+            return Err(RentalAgreementError::AgreementNotTerminated.into());
+        }
+
+        Self::verify_escrow_account(
+            escrow_account,
+            rent_agreement_account.key,
+            program_id,
+            rent_data.escrow_bump,
+        )?;
+
+        if flat_owner_account.key != &rent_data.flat_owner_pubkey {
+            msg!(
+                "{} Forfeiture recipient must match flat owner key used during agreement initialization",
+                LOG_TAG_NAME
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if tenant_account.key != &rent_data.tenant_pubkey {
+            msg!(
+                "{} Rent reserve recipient must match tenant key used during agreement initialization",
+                LOG_TAG_NAME
+            );
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        Self::settle_escrow(escrow_account, rent_data.deposit, flat_owner_account, tenant_account)?;
+
+        msg!(
+            "{} Deposit forfeited to flat owner; rent reserve returned to tenant",
+            LOG_TAG_NAME
+        );
+
+        Ok(())
+    }
+
+    /// Confirms `account` is rent-exempt at its current balance and data length,
+    /// logging the lamport shortfall when it is not. Reused for every program-owned
+    /// account the contract creates or touches (the agreement account and the
+    /// deposit escrow PDA) so state is never written behind an account that the
+    /// runtime could later purge for insufficient rent.
+    fn verify_rent_exemption(account: &AccountInfo, rent: &Rent) -> ProgramResult {
+        if !rent.is_exempt(account.lamports(), account.data_len()) {
+            let shortfall =
+                rent.minimum_balance(account.data_len()).saturating_sub(account.lamports());
+            msg!(
+                "{} Account {} not rent exempt; short by {} lamports",
+                LOG_TAG_NAME,
+                account.key,
+                shortfall
+            );
+            return Err(ProgramError::AccountNotRentExempt);
+        }
+        Ok(())
+    }
+
+    /// Asserts that `account` meets the writability/signer/ownership expectations
+    /// declared for it in the `RentalInstruction` doc comments. Pass `None` for
+    /// `expected_owner` when the account is a regular wallet rather than a
+    /// program-owned account.
+    fn validate_account(
+        account: &AccountInfo,
+        expected_owner: Option<&Pubkey>,
+        expect_writable: bool,
+        expect_signer: bool,
+    ) -> ProgramResult {
+        if expect_writable && !account.is_writable {
+            msg!("{} Account {} must be writable", LOG_TAG_NAME, account.key);
+            return Err(RentalAgreementError::NonWriteableAccount(*account.key).into());
+        }
+
+        if expect_signer && !account.is_signer {
+            msg!(
+                "{} Account {} must sign the transaction",
+                LOG_TAG_NAME,
+                account.key
+            );
+            return Err(RentalAgreementError::MissingSignature.into());
+        }
+
+        if let Some(owner) = expected_owner {
+            if account.owner != owner {
+                msg!(
+                    "{} Account {} has unexpected owner: {}",
+                    LOG_TAG_NAME,
+                    account.key,
+                    account.owner
+                );
+                return Err(RentalAgreementError::InvalidOwner(*account.key).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Confirms `escrow_account` is the PDA derived for `agreement_key` and is owned by this program.
+    fn verify_escrow_account(
+        escrow_account: &AccountInfo,
+        agreement_key: &Pubkey,
+        program_id: &Pubkey,
+        escrow_bump: u8,
+    ) -> ProgramResult {
+        let expected_escrow_key = Pubkey::create_program_address(
+            &[ESCROW_SEED_PREFIX, agreement_key.as_ref(), &[escrow_bump]],
+            program_id,
+        )
+        .map_err(|_| ProgramError::InvalidSeeds)?;
+
+        if escrow_account.key != &expected_escrow_key {
+            msg!(
+                "{} Deposit escrow account does not match the PDA derived from the agreement account",
+                LOG_TAG_NAME
+            );
+            return Err(RentalAgreementError::InvalidEscrowAccount.into());
+        }
+
+        if escrow_account.owner != program_id {
+            msg!(
+                "{} Deposit escrow account is not owned by this program",
+                LOG_TAG_NAME
+            );
+            return Err(ProgramError::IncorrectProgramId);
+        }
+
+        Ok(())
+    }
+
+    /// Splits the program-owned escrow account's balance in two: exactly `deposit`
+    /// lamports go to `deposit_recipient`, and whatever remains (the create-time
+    /// rent reserve the tenant funded via `create_account`) goes to
+    /// `reserve_recipient`. On a refund both recipients are the tenant; on a
+    /// forfeiture only the `deposit` portion goes to the flat owner and the
+    /// reserve still returns to the tenant who funded it.
+    fn settle_escrow<'a>(
+        escrow_account: &AccountInfo<'a>,
+        deposit: u64,
+        deposit_recipient: &AccountInfo<'a>,
+        reserve_recipient: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        let rent = Rent::get()?;
+        let rent_state_snapshot = [
+            (escrow_account, RentState::from_account(escrow_account, &rent)),
+            (deposit_recipient, RentState::from_account(deposit_recipient, &rent)),
+            (reserve_recipient, RentState::from_account(reserve_recipient, &rent)),
+        ];
+
+        let (deposit_lamports, reserve_lamports) =
+            Self::split_escrow_balance(escrow_account.lamports(), deposit);
+
+        **escrow_account.try_borrow_mut_lamports()? -= deposit_lamports + reserve_lamports;
+        **deposit_recipient.try_borrow_mut_lamports()? += deposit_lamports;
+        **reserve_recipient.try_borrow_mut_lamports()? += reserve_lamports;
+
+        Self::assert_valid_rent_transitions(&rent, &rent_state_snapshot)
+    }
+
+    /// Splits an escrow balance of `escrow_lamports` into the `deposit` portion
+    /// and the create-time rent reserve that remains, capping the deposit portion
+    /// at whatever the escrow actually holds.
+    fn split_escrow_balance(escrow_lamports: u64, deposit: u64) -> (u64, u64) {
+        let deposit_lamports = deposit.min(escrow_lamports);
+        let reserve_lamports = escrow_lamports - deposit_lamports;
+        (deposit_lamports, reserve_lamports)
+    }
+
+    /// Confirms every `(account, pre-transfer state)` pair in `snapshot` still satisfies
+    /// [`RentState::transition_allowed`] against the account's current, post-transfer state.
+    fn assert_valid_rent_transitions(
+        rent: &Rent,
+        snapshot: &[(&AccountInfo, RentState)],
+    ) -> ProgramResult {
+        for (account, pre_state) in snapshot {
+            let post_state = RentState::from_account(account, rent);
+            if !RentState::transition_allowed(pre_state, &post_state) {
+                msg!(
+                    "{} Account {} left in an invalid rent-paying state",
+                    LOG_TAG_NAME,
+                    account.key
+                );
+                return Err(RentalAgreementError::InvalidRentPayingAccount.into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refund_sends_the_whole_balance_to_the_tenant() {
+        // On a refund, deposit_recipient and reserve_recipient are both the
+        // tenant, so the full escrow balance (deposit + create-time reserve)
+        // should be accounted for between the two halves of the split.
+        let reserve = 890_880; // Rent::minimum_balance(0) on a typical cluster
+        let deposit = 5_000_000;
+        let (deposit_lamports, reserve_lamports) =
+            Processor::split_escrow_balance(deposit + reserve, deposit);
+        assert_eq!(deposit_lamports, deposit);
+        assert_eq!(reserve_lamports, reserve);
+    }
+
+    #[test]
+    fn forfeiture_keeps_the_create_time_reserve_out_of_the_flat_owners_share() {
+        let reserve = 890_880;
+        let deposit = 5_000_000;
+        let (deposit_lamports, reserve_lamports) =
+            Processor::split_escrow_balance(deposit + reserve, deposit);
+        assert_eq!(deposit_lamports, deposit, "flat owner must get exactly the deposit");
+        assert_eq!(reserve_lamports, reserve, "tenant must get back the reserve it funded");
+    }
+
+    #[test]
+    fn deposit_portion_is_capped_at_whatever_the_escrow_actually_holds() {
+        // Defensive: even if the escrow somehow holds less than `deposit`, the
+        // split must not be able to claim more than is actually there.
+        let (deposit_lamports, reserve_lamports) = Processor::split_escrow_balance(100, 5_000_000);
+        assert_eq!(deposit_lamports, 100);
+        assert_eq!(reserve_lamports, 0);
+    }
 }
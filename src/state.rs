@@ -1,9 +1,15 @@
 use borsh::{BorshDeserialize, BorshSerialize};
 use solana_program::{
+    account_info::AccountInfo,
     program_pack::{IsInitialized, Sealed},
     pubkey::Pubkey,
+    sysvar::rent::Rent,
 };
 
+/// Seed prefix for the PDA that escrows a tenant's security deposit for a given
+/// rent agreement account.
+pub const ESCROW_SEED_PREFIX: &[u8] = b"escrow";
+
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub struct RentalAgreementAccount {
     pub status: u8,
@@ -14,6 +20,9 @@ pub struct RentalAgreementAccount {
     pub duration: u64,
     pub duration_unit: u8,
     pub remaining_payments: u64,
+    pub escrow_bump: u8,
+    pub start_timestamp: i64,
+    pub last_payment_timestamp: i64,
 }
 
 impl Sealed for RentalAgreementAccount {}
@@ -25,6 +34,20 @@ impl IsInitialized for RentalAgreementAccount {
 }
 
 impl RentalAgreementAccount {
+    /// Serialized size of the account, in bytes: the sum of every field's Borsh
+    /// encoding. Clients must size the account with exactly this many bytes.
+    pub const LEN: usize = 1 // status
+        + 32 // flat_owner_pubkey
+        + 32 // tenant_pubkey
+        + 8 // deposit
+        + 8 // rent_amount
+        + 8 // duration
+        + 1 // duration_unit
+        + 8 // remaining_payments
+        + 1 // escrow_bump
+        + 8 // start_timestamp
+        + 8; // last_payment_timestamp
+
     pub fn is_complete(&self) -> bool {
         self.status == AgreementStatus::Completed as u8
     }
@@ -32,11 +55,51 @@ impl RentalAgreementAccount {
     pub fn is_terminated(&self) -> bool {
         self.status == AgreementStatus::Terminated as u8
     }
+
+    /// Derives the PDA (and its bump) that escrows the security deposit for the
+    /// rent agreement account living at `agreement_key`.
+    pub fn escrow_pda(agreement_key: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[ESCROW_SEED_PREFIX, agreement_key.as_ref()],
+            program_id,
+        )
+    }
+
+    /// The unix timestamp at which the lease itself (not just the remaining
+    /// payments) is considered to have run its course.
+    pub fn expiry_timestamp(&self) -> Option<i64> {
+        let interval_seconds = Duration::from_u8(self.duration_unit)?.seconds();
+        self.start_timestamp
+            .checked_add(interval_seconds.checked_mul(self.duration as i64)?)
+    }
 }
 
 #[derive(BorshSerialize, BorshDeserialize, Debug)]
 pub enum Duration {
-    Months = 0,
+    Days = 0,
+    Weeks,
+    Months,
+}
+
+impl Duration {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(Duration::Days),
+            1 => Some(Duration::Weeks),
+            2 => Some(Duration::Months),
+            _ => None,
+        }
+    }
+
+    /// Length of one payment interval, in seconds.
+    pub fn seconds(&self) -> i64 {
+        const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+        match self {
+            Duration::Days => SECONDS_PER_DAY,
+            Duration::Weeks => 7 * SECONDS_PER_DAY,
+            Duration::Months => 30 * SECONDS_PER_DAY,
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -46,3 +109,82 @@ pub enum AgreementStatus {
     Completed,
     Terminated,
 }
+
+/// Classifies an account's relationship to the rent-exemption threshold, mirroring
+/// the states the runtime itself recognizes when deciding whether a lamport
+/// transfer is allowed to leave an account partially rent-paying.
+#[derive(Debug, PartialEq, Eq)]
+pub enum RentState {
+    Uninitialized,
+    RentPaying(usize),
+    RentExempt,
+}
+
+impl RentState {
+    /// Classifies `account` against `rent` as of the current instant.
+    pub fn from_account(account: &AccountInfo, rent: &Rent) -> Self {
+        let lamports = account.lamports();
+        if lamports == 0 {
+            RentState::Uninitialized
+        } else if lamports < rent.minimum_balance(account.data_len()) {
+            RentState::RentPaying(account.data_len())
+        } else {
+            RentState::RentExempt
+        }
+    }
+
+    /// An account may only land in `RentPaying` after a transaction if it was
+    /// already `RentPaying` with the same data size beforehand; any other
+    /// pre-state must not transition into `RentPaying`. `Uninitialized` and
+    /// `RentExempt` post-states are always allowed.
+    pub fn transition_allowed(pre: &RentState, post: &RentState) -> bool {
+        match post {
+            RentState::RentPaying(post_len) => match pre {
+                RentState::RentPaying(pre_len) => pre_len == post_len,
+                _ => false,
+            },
+            RentState::Uninitialized | RentState::RentExempt => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rent_paying_may_only_transition_to_rent_paying_of_the_same_size() {
+        assert!(RentState::transition_allowed(
+            &RentState::RentPaying(10),
+            &RentState::RentPaying(10)
+        ));
+        assert!(!RentState::transition_allowed(
+            &RentState::RentPaying(10),
+            &RentState::RentPaying(11)
+        ));
+    }
+
+    #[test]
+    fn uninitialized_or_rent_exempt_may_not_become_rent_paying() {
+        assert!(!RentState::transition_allowed(
+            &RentState::Uninitialized,
+            &RentState::RentPaying(10)
+        ));
+        assert!(!RentState::transition_allowed(
+            &RentState::RentExempt,
+            &RentState::RentPaying(10)
+        ));
+    }
+
+    #[test]
+    fn any_pre_state_may_become_uninitialized_or_rent_exempt() {
+        for pre in [
+            RentState::Uninitialized,
+            RentState::RentPaying(10),
+            RentState::RentExempt,
+        ] {
+            assert!(RentState::transition_allowed(&pre, &RentState::Uninitialized));
+            assert!(RentState::transition_allowed(&pre, &RentState::RentExempt));
+        }
+    }
+}